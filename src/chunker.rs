@@ -0,0 +1,130 @@
+//! Content-defined chunkers.
+//!
+//! Two interchangeable chunking strategies are supported: FastCDC (the
+//! default, tunable via min/avg/max target sizes) and AE (Asymmetric
+//! Extremum), a hash-free chunker that finds cut points purely by tracking
+//! the position of the running maximum byte within a window. AE trades a
+//! little dedup ratio for being substantially cheaper to compute, since it
+//! does no rolling-hash arithmetic at all.
+//!
+//! Whichever chunker and parameters are used for a store's first `add` are
+//! persisted to `.blobular/config` so later adds to the same store can't
+//! silently produce incompatible chunk boundaries.
+
+use fastcdc::v2020;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkerKind {
+    FastCdc,
+    Ae,
+}
+
+impl ChunkerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkerKind::FastCdc => "fastcdc",
+            ChunkerKind::Ae => "ae",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "fastcdc" => Ok(ChunkerKind::FastCdc),
+            "ae" => Ok(ChunkerKind::Ae),
+            other => Err(format!("unknown chunker: {}", other)),
+        }
+    }
+}
+
+/// A content-defined chunk boundary within a buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk {
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Parameters for whichever chunker a store was initialized with. For AE,
+/// only `avg` is meaningful and is used directly as the window size `w`
+/// (expected chunk size is approximately `w`); `min`/`max` are carried along
+/// unused so the on-disk format doesn't need a second shape per chunker.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkerParams {
+    pub kind: ChunkerKind,
+    pub min: u32,
+    pub avg: u32,
+    pub max: u32,
+}
+
+impl ChunkerParams {
+    pub fn chunk(&self, buf: &[u8]) -> Vec<Chunk> {
+        match self.kind {
+            ChunkerKind::FastCdc => v2020::FastCDC::new(buf, self.min, self.avg, self.max)
+                .map(|chunk| Chunk {
+                    offset: chunk.offset,
+                    length: chunk.length,
+                })
+                .collect(),
+            ChunkerKind::Ae => ae_chunks(buf, self.avg as usize),
+        }
+    }
+}
+
+/// AE (Asymmetric Extremum) chunking.
+///
+/// Scans the buffer tracking the running maximum byte value and its
+/// position. If a new maximum is found, the window restarts from there. If
+/// `window` bytes pass since the last new maximum without one being beaten,
+/// a boundary is cut at the current position and the search restarts from
+/// the next byte. Any remainder with no boundary found becomes a final
+/// chunk.
+fn ae_chunks(buf: &[u8], window: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+
+    if buf.is_empty() {
+        return chunks;
+    }
+
+    if window == 0 {
+        chunks.push(Chunk {
+            offset: 0,
+            length: buf.len(),
+        });
+        return chunks;
+    }
+
+    let mut start = 0usize;
+    let mut max_value = buf[start];
+    let mut max_pos = start;
+    let mut i = start + 1;
+
+    while i < buf.len() {
+        if buf[i] > max_value {
+            max_value = buf[i];
+            max_pos = i;
+            i += 1;
+        } else if i == max_pos + window {
+            chunks.push(Chunk {
+                offset: start,
+                length: i - start + 1,
+            });
+            start = i + 1;
+            if start >= buf.len() {
+                break;
+            }
+            max_value = buf[start];
+            max_pos = start;
+            i = start + 1;
+        } else {
+            i += 1;
+        }
+    }
+
+    if start < buf.len() {
+        chunks.push(Chunk {
+            offset: start,
+            length: buf.len() - start,
+        });
+    }
+
+    chunks
+}
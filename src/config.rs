@@ -0,0 +1,88 @@
+//! Persisted store configuration.
+//!
+//! `.blobular/config` is a tiny `key=value` file written once at `init` and
+//! read back by every other command, so the store's hash algorithm stays
+//! consistent without being re-specified on each invocation. The chunker is
+//! persisted lazily, the first time `add` actually needs one, since `init`
+//! doesn't ask for chunking parameters.
+
+use std::path::Path;
+
+use crate::chunker::{ChunkerKind, ChunkerParams};
+use crate::hash::HashType;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub hash_type: HashType,
+    pub chunker: Option<ChunkerParams>,
+}
+
+impl Config {
+    pub fn write(dot_blobular: &Path, config: &Config) {
+        let mut contents = format!("hash_type={}\n", config.hash_type.as_str());
+        if let Some(chunker) = config.chunker {
+            contents.push_str(&format!("chunker_kind={}\n", chunker.kind.as_str()));
+            contents.push_str(&format!("chunker_min={}\n", chunker.min));
+            contents.push_str(&format!("chunker_avg={}\n", chunker.avg));
+            contents.push_str(&format!("chunker_max={}\n", chunker.max));
+        }
+        std::fs::write(dot_blobular.join("config"), contents).unwrap();
+    }
+
+    pub fn read(dot_blobular: &Path) -> Config {
+        let contents = std::fs::read_to_string(dot_blobular.join("config")).unwrap_or_else(|_| {
+            eprintln!("fatal: missing .blobular/config; is this a valid blobular repository?");
+            std::process::exit(128);
+        });
+
+        let mut hash_type = None;
+        let mut chunker_kind = None;
+        let mut chunker_min = None;
+        let mut chunker_avg = None;
+        let mut chunker_max = None;
+
+        for line in contents.lines() {
+            if let Some(value) = line.strip_prefix("hash_type=") {
+                hash_type = Some(HashType::from_str(value.trim()).unwrap_or_else(|err| {
+                    eprintln!("fatal: invalid .blobular/config: {}", err);
+                    std::process::exit(128);
+                }));
+            } else if let Some(value) = line.strip_prefix("chunker_kind=") {
+                chunker_kind = Some(ChunkerKind::from_str(value.trim()).unwrap_or_else(|err| {
+                    eprintln!("fatal: invalid .blobular/config: {}", err);
+                    std::process::exit(128);
+                }));
+            } else if let Some(value) = line.strip_prefix("chunker_min=") {
+                chunker_min = value.trim().parse::<u32>().ok();
+            } else if let Some(value) = line.strip_prefix("chunker_avg=") {
+                chunker_avg = value.trim().parse::<u32>().ok();
+            } else if let Some(value) = line.strip_prefix("chunker_max=") {
+                chunker_max = value.trim().parse::<u32>().ok();
+            }
+        }
+
+        let hash_type = hash_type.unwrap_or_else(|| {
+            eprintln!("fatal: .blobular/config is missing a hash_type entry");
+            std::process::exit(128);
+        });
+
+        let chunker = match (chunker_kind, chunker_min, chunker_avg, chunker_max) {
+            (Some(kind), Some(min), Some(avg), Some(max)) => Some(ChunkerParams {
+                kind,
+                min,
+                avg,
+                max,
+            }),
+            _ => None,
+        };
+
+        Config { hash_type, chunker }
+    }
+
+    /// Persist the chunker choice for a store that doesn't have one yet.
+    pub fn set_chunker(dot_blobular: &Path, chunker: ChunkerParams) {
+        let mut config = Config::read(dot_blobular);
+        config.chunker = Some(chunker);
+        Config::write(dot_blobular, &config);
+    }
+}
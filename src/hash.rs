@@ -0,0 +1,102 @@
+//! Pluggable hash backends for content addressing.
+//!
+//! The store is addressed by the hex digest of whichever [`HashType`] was
+//! selected at `init` time. A single store must not mix algorithms, since
+//! prefix lookup depends on the digest length: an argument as long as a full
+//! digest is taken as a complete hash rather than a prefix.
+
+use sha1::{Digest as Sha1Digest, Sha1};
+
+/// The hash algorithm used to address objects in a blobular store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashType {
+    Sha1,
+    Blake3,
+    Xxh3,
+}
+
+impl HashType {
+    /// Length, in hex characters, of a digest produced by this hash type.
+    pub fn digest_hex_len(&self) -> usize {
+        match self {
+            HashType::Sha1 => 40,
+            HashType::Blake3 => 64,
+            HashType::Xxh3 => 32,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashType::Sha1 => "sha1",
+            HashType::Blake3 => "blake3",
+            HashType::Xxh3 => "xxh3",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "sha1" => Ok(HashType::Sha1),
+            "blake3" => Ok(HashType::Blake3),
+            "xxh3" => Ok(HashType::Xxh3),
+            other => Err(format!("unknown hash type: {}", other)),
+        }
+    }
+
+    /// Construct a fresh hasher for this hash type.
+    pub fn hasher(&self) -> Box<dyn Hasher> {
+        match self {
+            HashType::Sha1 => Box::new(Sha1Hasher(Sha1::new())),
+            HashType::Blake3 => Box::new(Blake3Hasher(blake3::Hasher::new())),
+            HashType::Xxh3 => Box::new(Xxh3Hasher(xxhash_rust::xxh3::Xxh3::new())),
+        }
+    }
+}
+
+/// A streaming hasher producing a lowercase hex digest.
+pub trait Hasher {
+    fn update(&mut self, bytes: &[u8]);
+    fn finalize(self: Box<Self>) -> String;
+}
+
+struct Sha1Hasher(Sha1);
+
+impl Hasher for Sha1Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        Sha1Digest::update(&mut self.0, bytes);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        format!("{:x}", self.0.finalize())
+    }
+}
+
+struct Blake3Hasher(blake3::Hasher);
+
+impl Hasher for Blake3Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        self.0.finalize().to_hex().to_string()
+    }
+}
+
+struct Xxh3Hasher(xxhash_rust::xxh3::Xxh3);
+
+impl Hasher for Xxh3Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        format!("{:032x}", self.0.digest128())
+    }
+}
+
+/// Hash a byte slice in one shot with the given hash type.
+pub fn hash_bytes(hash_type: HashType, bytes: &[u8]) -> String {
+    let mut hasher = hash_type.hasher();
+    hasher.update(bytes);
+    hasher.finalize()
+}
@@ -1,14 +1,24 @@
-use sha1::{Digest, Sha1};
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{Parser, Subcommand};
-use fastcdc::v2020;
 
-/// Split a file into content-defined chunks.
-pub fn chunk_file(buf: &[u8]) -> impl Iterator<Item = v2020::Chunk> + '_ {
-    v2020::FastCDC::new(buf, 2048, 4096, 65535)
-}
+mod chunker;
+mod config;
+mod hash;
+mod merkle;
+mod refs;
+mod store;
+mod tree;
+
+use chunker::{ChunkerKind, ChunkerParams};
+use config::Config;
+use hash::HashType;
+use store::ObjectStore;
+
+const DEFAULT_MIN: u32 = 2048;
+const DEFAULT_AVG: u32 = 4096;
+const DEFAULT_MAX: u32 = 65535;
 
 /// Command-line interface to `blobular`.
 #[derive(Debug, Parser)]
@@ -22,14 +32,37 @@ struct Cli {
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Initialize a new blobular store in the current directory.
-    Init,
+    Init {
+        /// Hash algorithm used to address objects in this store.
+        #[arg(long, default_value = "sha1")]
+        hash: String,
+    },
 
-    /// Add a blob to the store.
+    /// Add a file or directory to the store. Directories are snapshotted
+    /// recursively as a tree object.
     #[command(arg_required_else_help = true)]
     Add {
         /// Path to add.
         #[arg(required = true)]
         path: Vec<PathBuf>,
+
+        /// Chunker to use: `fastcdc` or `ae`. Only takes effect on the
+        /// store's first `add`; later adds reuse whatever was chosen then.
+        #[arg(long)]
+        chunker: Option<String>,
+
+        /// Minimum chunk size in bytes (FastCDC only).
+        #[arg(long)]
+        min: Option<u32>,
+
+        /// Average/target chunk size in bytes (FastCDC), or the window `w`
+        /// (AE).
+        #[arg(long)]
+        avg: Option<u32>,
+
+        /// Maximum chunk size in bytes (FastCDC only).
+        #[arg(long)]
+        max: Option<u32>,
     },
 
     /// Print a blob from the store.
@@ -49,6 +82,32 @@ enum Commands {
         #[arg(required = true)]
         hash: String,
     },
+
+    /// Report deduplication ratio and storage savings for the store.
+    Stats,
+
+    /// Recompute and check every digest reachable from a tree root.
+    #[command(arg_required_else_help = true)]
+    Verify {
+        /// Hash of the object to verify.
+        #[arg(required = true)]
+        hash: String,
+    },
+
+    /// Re-hash every stored object and check it against its address.
+    Fsck,
+
+    /// Rebuild a directory snapshot on disk from a tree hash.
+    #[command(arg_required_else_help = true)]
+    Restore {
+        /// Hash of the tree to restore.
+        #[arg(required = true)]
+        hash: String,
+
+        /// Destination directory to recreate the snapshot in.
+        #[arg(required = true)]
+        dest: PathBuf,
+    },
 }
 
 /// Find the `.blobular` directory. Uses the same logic as `git`.
@@ -75,7 +134,7 @@ fn find_dot_blobular() -> Result<PathBuf, ()> {
 }
 
 /// Initialize a new blobular store in the current directory.
-fn initialize_dot_blobular() {
+fn initialize_dot_blobular(hash: &str) {
     // Check that we are not already (nested) in a blobular repository.
     if let Ok(dot_blobular) = find_dot_blobular() {
         eprintln!("fatal: already inside a blobular repository");
@@ -86,35 +145,69 @@ fn initialize_dot_blobular() {
         std::process::exit(128);
     }
 
+    let hash_type = HashType::from_str(hash).unwrap_or_else(|err| {
+        eprintln!("fatal: {}", err);
+        std::process::exit(128);
+    });
+
     // Create the `.blobular` directory.
     std::fs::create_dir(".blobular").unwrap();
 
-    // Create the `objects` directory.
-    std::fs::create_dir(".blobular/objects").unwrap();
+    // Opening the store creates the pack file and index.
+    ObjectStore::open(&PathBuf::from(".blobular"));
+
+    // Persist the chosen hash algorithm so every other command reads it back.
+    // The chunker isn't chosen yet; it's persisted lazily on the first `add`.
+    Config::write(
+        &PathBuf::from(".blobular"),
+        &Config {
+            hash_type,
+            chunker: None,
+        },
+    );
 }
 
-/// Compute the SHA-1 hash of a file.
-fn hash_file(path: &PathBuf) -> Result<String, std::io::Error> {
-    // Open the file.
-    let mut file = std::fs::File::open(path)?;
-
-    // Create a hasher object.
-    let mut hasher = Sha1::new();
-
-    // Copy the entire file into the hasher.
-    std::io::copy(&mut file, &mut hasher)?;
+/// Resolve the chunker this store should use for the file currently being
+/// added, persisting it if this is the store's first `add`.
+fn resolve_chunker_params(
+    dot_blobular: &Path,
+    chunker: &Option<String>,
+    min: Option<u32>,
+    avg: Option<u32>,
+    max: Option<u32>,
+) -> ChunkerParams {
+    if let Some(chunker) = Config::read(dot_blobular).chunker {
+        return chunker;
+    }
 
-    // Compute the hash.
-    let hash = hasher.finalize();
+    let kind = match chunker {
+        Some(chunker) => ChunkerKind::from_str(chunker).unwrap_or_else(|err| {
+            eprintln!("fatal: {}", err);
+            std::process::exit(128);
+        }),
+        None => ChunkerKind::FastCdc,
+    };
 
-    // Convert the hash to a hex string.
-    let hash = format!("{:x}", hash);
+    let params = ChunkerParams {
+        kind,
+        min: min.unwrap_or(DEFAULT_MIN),
+        avg: avg.unwrap_or(DEFAULT_AVG),
+        max: max.unwrap_or(DEFAULT_MAX),
+    };
 
-    Ok(hash)
+    Config::set_chunker(dot_blobular, params);
+    params
 }
 
-/// Add a file to the blobular repository.
-fn add_file_to_blobular_repo(path: PathBuf) {
+/// Add a path (file or directory) to the blobular repository, printing its
+/// resulting hash.
+fn add_path_to_blobular_repo(
+    path: PathBuf,
+    chunker: &Option<String>,
+    min: Option<u32>,
+    avg: Option<u32>,
+    max: Option<u32>,
+) {
     // Check that we are in a blobular repository.
     let dot_blobular = match find_dot_blobular() {
         Ok(dot_blobular) => dot_blobular,
@@ -125,12 +218,79 @@ fn add_file_to_blobular_repo(path: PathBuf) {
         }
     };
 
-    // Check that the file exists.
-    if !path.is_file() {
+    if !path.exists() {
         eprintln!("fatal: pathspec '{:?}' did not match any files", path);
         std::process::exit(128);
     }
 
+    // The store was initialized with a fixed hash algorithm; every add must
+    // use it so the store never mixes digests of different lengths.
+    let hash_type = Config::read(&dot_blobular).hash_type;
+    let object_store = ObjectStore::open(&dot_blobular);
+    let chunker_params = resolve_chunker_params(&dot_blobular, chunker, min, avg, max);
+
+    let (_mode, hash) = add_path(&path, &object_store, hash_type, chunker_params);
+
+    // Remember it as a top-level root so `stats` can walk everything it
+    // transitively references — chunks, Merkle interior nodes, and (for a
+    // directory) the tree objects listing its entries — and tell them apart
+    // from unreferenced unique chunks.
+    refs::record(&dot_blobular, &hash);
+
+    println!("{}", hash);
+}
+
+/// Recursively add `path`, returning its mode and resulting hash. A file's
+/// hash is a Merkle root; a directory's hash is a tree object listing its
+/// entries.
+fn add_path(
+    path: &Path,
+    object_store: &ObjectStore,
+    hash_type: HashType,
+    chunker_params: ChunkerParams,
+) -> (u32, String) {
+    let metadata = path.metadata().unwrap();
+
+    if metadata.is_dir() {
+        // Never snapshot the store's own bookkeeping directory, analogous to
+        // git ignoring `.git`. Without this, `add .` from the repo root would
+        // recurse into the pack file and index themselves, and every
+        // subsequent `add .` would bake in another (growing) copy of them.
+        let mut dir_entries: Vec<_> = std::fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_name() != ".blobular")
+            .collect();
+        dir_entries.sort_by_key(|entry| entry.file_name());
+
+        let mut entries = Vec::new();
+        for dir_entry in dir_entries {
+            let child_path = dir_entry.path();
+            let (mode, hash) = add_path(&child_path, object_store, hash_type, chunker_params);
+            entries.push(tree::TreeEntry {
+                mode,
+                name: dir_entry.file_name().to_string_lossy().to_string(),
+                hash,
+            });
+        }
+
+        let tree_hash = tree::store_tree(object_store, hash_type, &entries);
+        (tree::TYPE_DIR, tree_hash)
+    } else {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = tree::TYPE_FILE | (metadata.permissions().mode() & 0o777);
+        let root_hash = add_file(path, object_store, hash_type, chunker_params);
+        (mode, root_hash)
+    }
+}
+
+/// Chunk and store a single file, returning its Merkle root.
+fn add_file(
+    path: &Path,
+    object_store: &ObjectStore,
+    hash_type: HashType,
+    chunker_params: ChunkerParams,
+) -> String {
     // Check that the file is not empty.
     if path.metadata().unwrap().len() == 0 {
         eprintln!("fatal: empty file: {:?}", path);
@@ -138,126 +298,63 @@ fn add_file_to_blobular_repo(path: PathBuf) {
     }
 
     // Chunk the file into content-defined chunks.
-    let file = std::fs::File::open(&path).unwrap();
+    let file = std::fs::File::open(path).unwrap();
     let file_bytes = std::io::BufReader::new(file)
         .bytes()
         .flatten()
         .collect::<Vec<_>>();
-    let chunks = chunk_file(&file_bytes);
-
-    // Compute the hash of the whole file.
-    // NOTE: should build up the hash as we iterate the chunks of the file rather than
-    // iterating through the same file multiple times.
-    let blob_hash = hash_file(&path).unwrap();
+    let chunks = chunker_params.chunk(&file_bytes);
 
-    // Maintain a list of chunk hashes so we can write out the parent blob at the end.
+    // Maintain a list of chunk hashes so we can build the Merkle tree over
+    // them once they're all stored.
     let mut chunk_hashes = Vec::new();
 
     // Store the chunks in the blobular repository.
     for chunk in chunks {
         let chunk_bytes = &file_bytes[chunk.offset..chunk.offset + chunk.length];
 
-        // Calculate the SHA1 hash of the chunk.
-        let mut hasher = Sha1::new();
-        hasher.update(&chunk_bytes);
-        let hash = hasher.finalize();
-        let chunk_hash = format!("{:x}", hash);
+        let chunk_hash = hash::hash_bytes(hash_type, chunk_bytes);
         chunk_hashes.push(chunk_hash.clone());
-        store_blob(&dot_blobular, &chunk_bytes, &chunk_hash);
-    }
-
-    // Build the parent blob.
-    // It is formatted as:
-    // blob <sub-blob-hash>
-    // blob <sub-blob-hash>
-    // etc.
-
-    let mut parent_blob = Vec::new();
-    for chunk_hash in chunk_hashes {
-        parent_blob.extend_from_slice(b"blob ");
-        parent_blob.extend_from_slice(chunk_hash.as_bytes());
-        parent_blob.push(b'\n');
+        object_store.store(&chunk_hash, chunk_bytes, store::ObjectType::Chunk);
     }
 
-    // Store the parent blob.
-    store_blob(&dot_blobular, &parent_blob, &blob_hash);
-
-    // Print the hash of the parent blob.
-    println!("{}", blob_hash);
+    // Build the Merkle tree over the chunk hashes; its root is the file's
+    // top-level hash.
+    merkle::build_root(object_store, hash_type, chunk_hashes)
 }
 
-/// Store a blob in the blobular repository.
-fn store_blob(dot_blobular: &PathBuf, blob: &[u8], blob_hash: &str) {
-    // Compute the path to the object. The first two characters of the hash are
-    // the directory name, and the rest of the hash is the file name.
-    let object_path = dot_blobular
-        .join("objects")
-        .join(&blob_hash[..2])
-        .join(&blob_hash[2..]);
-
-    // If the object already exists, exit immediately with no error. This is just a no-op.
-    if object_path.is_file() {
-        return;
+/// Retrieve the full hash from a prefix.
+fn full_hash_from_prefix(prefix: &str, object_store: &ObjectStore, hash_type: HashType) -> String {
+    if prefix.len() == hash_type.digest_hex_len() {
+        return prefix.to_string();
     }
 
-    // Check that the object's directory exists, and create it if not.
-    if let Some(parent) = object_path.parent() {
-        if !parent.is_dir() {
-            std::fs::create_dir_all(parent).unwrap();
-        }
+    if prefix.len() < 4 {
+        eprintln!("fatal: ambiguous argument: {}", prefix);
+        eprintln!("note: minimum length of a hash is 4 characters");
+        std::process::exit(128);
     }
 
-    // Compress the blob with zlib using flate2 and write it to the object store.
-    let object_file = std::fs::File::create(&object_path).unwrap();
-    let mut encoder = flate2::write::ZlibEncoder::new(object_file, flate2::Compression::default());
-    let mut blob_reader = std::io::BufReader::new(blob);
-    std::io::copy(&mut blob_reader, &mut encoder).unwrap();
-    encoder.finish().unwrap();
-}
+    let mut matching_objects = object_store.hashes_with_prefix(prefix);
 
-/// Retrieve the full hash from a prefix.
-fn full_hash_from_prefix(prefix: &str, dot_blobular: &PathBuf) -> String {
-    let hash = if prefix.len() == 40 {
-        prefix.to_string()
-    } else {
-        if prefix.len() < 4 {
-            eprintln!("fatal: ambiguous argument: {}", prefix);
-            eprintln!("note: minimum length of a hash is 4 characters");
-            std::process::exit(128);
-        }
-
-        // Find all objects that start with `hash`.
-        let object_dir = dot_blobular.join("objects").join(&prefix[..2]);
-        let mut matching_objects = Vec::new();
-        for entry in std::fs::read_dir(object_dir).unwrap() {
-            let entry = entry.unwrap();
-            let entry = entry.file_name();
-            let entry = entry.to_str().unwrap();
-            if entry.starts_with(&prefix[2..]) {
-                matching_objects.push(entry.to_string());
-            }
-        }
-
-        // If there are no matching objects, exit with an error.
-        if matching_objects.is_empty() {
-            eprintln!("fatal: object not found: {}", prefix);
-            std::process::exit(128);
-        }
+    // If there are no matching objects, exit with an error.
+    if matching_objects.is_empty() {
+        eprintln!("fatal: object not found: {}", prefix);
+        std::process::exit(128);
+    }
 
-        // If there is more than one matching object, exit with an error.
-        if matching_objects.len() > 1 {
-            eprintln!("fatal: ambiguous argument: {}", prefix);
-            eprintln!("note: the following objects start with the given hash:");
-            for object in matching_objects {
-                eprintln!("note:   {}", object);
-            }
-            std::process::exit(128);
+    // If there is more than one matching object, exit with an error.
+    if matching_objects.len() > 1 {
+        matching_objects.sort();
+        eprintln!("fatal: ambiguous argument: {}", prefix);
+        eprintln!("note: the following objects start with the given hash:");
+        for object in matching_objects {
+            eprintln!("note:   {}", object);
         }
+        std::process::exit(128);
+    }
 
-        // There is exactly one matching object. Use it. We print the full hash, including object dir.
-        format!("{}{}", &prefix[..2], matching_objects[0])
-    };
-    hash
+    matching_objects.remove(0)
 }
 
 /// Print a blob from the store.
@@ -281,31 +378,24 @@ fn cat_blob_from_blobular_repo(hash: String) {
     };
 
     // `hash` can be a prefix of the full hash. Find the full hash.
-    let hash = full_hash_from_prefix(&hash, &dot_blobular);
-
-    // Compute the path to the object.
-    let object_path = dot_blobular
-        .join("objects")
-        .join(&hash[..2])
-        .join(&hash[2..]);
+    let hash_type = Config::read(&dot_blobular).hash_type;
+    let object_store = ObjectStore::open(&dot_blobular);
+    let hash = full_hash_from_prefix(&hash, &object_store, hash_type);
 
-    // Check that the object exists.
-    if !object_path.is_file() {
+    let blob = object_store.get(&hash).unwrap_or_else(|| {
         eprintln!("fatal: object not found: {}", hash);
         std::process::exit(128);
-    }
+    });
 
-    // Decompress the object with zlib using flate2 and write it to stdout.
-    let object_file = std::fs::File::open(&object_path).unwrap();
-    let mut decoder = flate2::read::ZlibDecoder::new(object_file);
-    std::io::copy(&mut decoder, &mut std::io::stdout()).unwrap();
+    std::io::Write::write_all(&mut std::io::stdout(), &blob).unwrap();
 }
 
 /// Print a file from the store.
 ///
-/// This prints the contents of the file. The blob hash that gets passed is expected to be
-/// in the format of a parent blob, i.e. it is expected to be a blob that contains the hashes
-/// of the chunks that make up the file. If not, this will fail.
+/// This prints the reconstructed contents of the file. The hash that gets
+/// passed is expected to be the root of a Merkle tree over the file's
+/// chunks (i.e. what `add` printed); the tree is walked in order, writing
+/// each leaf chunk's bytes to stdout.
 fn cat_file_from_blobular_repo(hash: String) {
     // Check that we are in a blobular repository.
     let dot_blobular = match find_dot_blobular() {
@@ -318,47 +408,224 @@ fn cat_file_from_blobular_repo(hash: String) {
     };
 
     // `hash` can be a prefix of the full hash. Find the full hash.
-    let hash = full_hash_from_prefix(&hash, &dot_blobular);
+    let hash_type = Config::read(&dot_blobular).hash_type;
+    let object_store = ObjectStore::open(&dot_blobular);
+    let hash = full_hash_from_prefix(&hash, &object_store, hash_type);
 
-    // Compute the path to the object.
-    let object_path = dot_blobular
-        .join("objects")
-        .join(&hash[..2])
-        .join(&hash[2..]);
-
-    // Check that the object exists.
-    if !object_path.is_file() {
-        eprintln!("fatal: object not found: {}", hash);
+    if let Err(err) = merkle::write_tree_bytes(&object_store, &hash, &mut std::io::stdout()) {
+        eprintln!("fatal: {}", err);
         std::process::exit(128);
     }
+}
 
-    // Decompress the object with zlib using flate2 and write it to stdout.
-    let object_file = std::fs::File::open(&object_path).unwrap();
-    let mut decoder = flate2::read::ZlibDecoder::new(object_file);
-    let mut blob = Vec::new();
-    decoder.read_to_end(&mut blob).unwrap();
-
-    // Split the blob into lines.
-    let parent_blob = String::from_utf8(blob).unwrap();
-    let parent_blob: Vec<&str> = parent_blob.trim().split("\n").collect();
-
-    // Verify the lines are of the form `blob <hash>` and extract the hash.
-    let blob_hashes = parent_blob
-        .iter()
-        .map(|line| {
-            let line = line.trim();
-            if !line.starts_with("blob ") {
-                eprintln!("fatal: invalid blob: {}", line);
-                std::process::exit(128);
+/// Report deduplication ratio and storage savings for the store.
+///
+/// Chunk bodies are the unique, physically-stored objects; everything else
+/// reachable from a top-level root (recorded in `.blobular/refs` — a file's
+/// Merkle root or a directory's tree hash) is overhead that just references
+/// them by hash, possibly more than once within a file, a directory, and
+/// across many top-level adds. Comparing the logical bytes referenced
+/// against the unique bytes actually stored gives the dedup ratio.
+fn stats_for_blobular_repo() {
+    // Check that we are in a blobular repository.
+    let dot_blobular = match find_dot_blobular() {
+        Ok(dot_blobular) => dot_blobular,
+        Err(()) => {
+            eprintln!("fatal: not a blobular repository (or any of the parent directories)");
+            eprintln!("run `blobular init` to create a new blobular repository");
+            std::process::exit(128);
+        }
+    };
+
+    let object_store = ObjectStore::open(&dot_blobular);
+    let roots = refs::read_all(&dot_blobular);
+
+    // Walk every root's Merkle/directory tree to find which stored objects
+    // are interior nodes (as opposed to chunks) and to sum the logical bytes
+    // referenced, counting duplicates.
+    let mut node_hashes = std::collections::HashSet::new();
+    let mut logical_bytes = 0u64;
+    for root in &roots {
+        merkle::walk_tree(&object_store, root, &mut node_hashes, &mut logical_bytes);
+    }
+
+    // Unique chunks are every stored object that isn't itself a tree node.
+    let mut unique_chunks = 0u64;
+    let mut stored_compressed_bytes = 0u64;
+    let mut stored_uncompressed_bytes = 0u64;
+    for (hash, entry) in object_store.iter() {
+        if node_hashes.contains(&hash) {
+            continue;
+        }
+        unique_chunks += 1;
+        stored_compressed_bytes += entry.compressed_len;
+        stored_uncompressed_bytes += entry.uncompressed_len;
+    }
+
+    let dedup_ratio = if stored_uncompressed_bytes > 0 {
+        logical_bytes as f64 / stored_uncompressed_bytes as f64
+    } else {
+        0.0
+    };
+    let percent_saved = if logical_bytes > 0 {
+        (1.0 - stored_uncompressed_bytes as f64 / logical_bytes as f64) * 100.0
+    } else {
+        0.0
+    };
+
+    println!("unique chunks:          {}", unique_chunks);
+    println!("stored bytes (compressed):   {}", stored_compressed_bytes);
+    println!("stored bytes (uncompressed): {}", stored_uncompressed_bytes);
+    println!("logical bytes referenced:    {}", logical_bytes);
+    println!("dedup ratio:             {:.2}x", dedup_ratio);
+    println!("space saved:             {:.1}%", percent_saved);
+}
+
+/// Recompute and check every digest reachable from a tree root.
+fn verify_hash_in_blobular_repo(hash: String) {
+    // Check that we are in a blobular repository.
+    let dot_blobular = match find_dot_blobular() {
+        Ok(dot_blobular) => dot_blobular,
+        Err(()) => {
+            eprintln!("fatal: not a blobular repository (or any of the parent directories)");
+            eprintln!("run `blobular init` to create a new blobular repository");
+            std::process::exit(128);
+        }
+    };
+
+    let hash_type = Config::read(&dot_blobular).hash_type;
+    let object_store = ObjectStore::open(&dot_blobular);
+    let hash = full_hash_from_prefix(&hash, &object_store, hash_type);
+
+    match merkle::verify(&object_store, hash_type, &hash) {
+        Ok(()) => println!("{}: ok", hash),
+        Err(err) => {
+            eprintln!("fatal: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Re-hash every stored object and check it against its address, and check
+/// that every child a tree node references actually exists.
+fn fsck_blobular_repo() {
+    // Check that we are in a blobular repository.
+    let dot_blobular = match find_dot_blobular() {
+        Ok(dot_blobular) => dot_blobular,
+        Err(()) => {
+            eprintln!("fatal: not a blobular repository (or any of the parent directories)");
+            eprintln!("run `blobular init` to create a new blobular repository");
+            std::process::exit(128);
+        }
+    };
+
+    let hash_type = Config::read(&dot_blobular).hash_type;
+    let object_store = ObjectStore::open(&dot_blobular);
+
+    let mut checked = 0u64;
+    let mut errors = 0u64;
+
+    for (hash, index_entry) in object_store.iter() {
+        checked += 1;
+
+        let bytes = match object_store.get(&hash) {
+            Some(bytes) => bytes,
+            None => {
+                println!("error: {}: object listed in index but unreadable", hash);
+                errors += 1;
+                continue;
             }
-            let hash = &line[5..];
-            hash
-        })
-        .collect::<Vec<_>>();
+        };
+
+        let recomputed = hash::hash_bytes(hash_type, &bytes);
+        if recomputed != hash {
+            println!("error: {}: hash mismatch (recomputed {})", hash, recomputed);
+            errors += 1;
+            continue;
+        }
+
+        // Dispatch on the stored object type rather than guessing from
+        // content, so a chunk whose bytes happen to look like a node or
+        // tree listing is never misread as one.
+        match index_entry.object_type {
+            store::ObjectType::Chunk => {}
+            store::ObjectType::Node => {
+                if let Ok(entries) = merkle::parse_entries(&bytes) {
+                    for entry in entries {
+                        if object_store.entry(entry.hash()).is_none() {
+                            println!("error: {}: missing child {}", hash, entry.hash());
+                            errors += 1;
+                        }
+                    }
+                }
+            }
+            store::ObjectType::Tree => {
+                if let Ok(entries) = tree::parse_entries(&bytes) {
+                    for entry in entries {
+                        if object_store.entry(&entry.hash).is_none() {
+                            println!("error: {}: missing child {}", hash, entry.hash);
+                            errors += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    println!("checked {} objects, {} errors", checked, errors);
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Rebuild a directory snapshot on disk from a tree hash.
+fn restore_tree_to_blobular_repo(hash: String, dest: PathBuf) {
+    // Check that we are in a blobular repository.
+    let dot_blobular = match find_dot_blobular() {
+        Ok(dot_blobular) => dot_blobular,
+        Err(()) => {
+            eprintln!("fatal: not a blobular repository (or any of the parent directories)");
+            eprintln!("run `blobular init` to create a new blobular repository");
+            std::process::exit(128);
+        }
+    };
+
+    let hash_type = Config::read(&dot_blobular).hash_type;
+    let object_store = ObjectStore::open(&dot_blobular);
+    let hash = full_hash_from_prefix(&hash, &object_store, hash_type);
+
+    restore_entry(&object_store, &hash, tree::TYPE_DIR, &dest);
+}
+
+/// Recreate the object addressed by `hash` at `dest`, using `mode` to decide
+/// whether it's a directory (tree object) or a file (Merkle root).
+fn restore_entry(object_store: &ObjectStore, hash: &str, mode: u32, dest: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    if mode & tree::TYPE_MASK == tree::TYPE_DIR {
+        std::fs::create_dir_all(dest).unwrap();
+
+        let bytes = object_store.get(hash).unwrap_or_else(|| {
+            eprintln!("fatal: object not found: {}", hash);
+            std::process::exit(128);
+        });
+        let entries = tree::parse_entries(&bytes).unwrap_or_else(|err| {
+            eprintln!("fatal: {}", err);
+            std::process::exit(128);
+        });
+
+        for entry in entries {
+            restore_entry(object_store, &entry.hash, entry.mode, &dest.join(&entry.name));
+        }
+    } else {
+        let mut file = std::fs::File::create(dest).unwrap();
+        if let Err(err) = merkle::write_tree_bytes(object_store, hash, &mut file) {
+            eprintln!("fatal: {}", err);
+            std::process::exit(128);
+        }
 
-    // For each line, print the blob.
-    for line in blob_hashes {
-        cat_blob_from_blobular_repo(line.to_string());
+        let permissions = std::fs::Permissions::from_mode(mode & 0o777);
+        std::fs::set_permissions(dest, permissions).unwrap();
     }
 }
 
@@ -366,12 +633,18 @@ fn main() {
     let args = Cli::parse();
 
     match args.command {
-        Commands::Init => {
-            initialize_dot_blobular();
+        Commands::Init { hash } => {
+            initialize_dot_blobular(&hash);
         }
-        Commands::Add { path } => {
+        Commands::Add {
+            path,
+            chunker,
+            min,
+            avg,
+            max,
+        } => {
             for path in path {
-                add_file_to_blobular_repo(path);
+                add_path_to_blobular_repo(path, &chunker, min, avg, max);
             }
         }
         Commands::CatBlob { hash } => {
@@ -380,5 +653,17 @@ fn main() {
         Commands::CatFile { hash } => {
             cat_file_from_blobular_repo(hash);
         }
+        Commands::Stats => {
+            stats_for_blobular_repo();
+        }
+        Commands::Verify { hash } => {
+            verify_hash_in_blobular_repo(hash);
+        }
+        Commands::Fsck => {
+            fsck_blobular_repo();
+        }
+        Commands::Restore { hash, dest } => {
+            restore_tree_to_blobular_repo(hash, dest);
+        }
     }
 }
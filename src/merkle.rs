@@ -0,0 +1,293 @@
+//! Merkle-tree object roots.
+//!
+//! A file used to be represented by one flat "parent blob" object: a
+//! newline-delimited list of `blob <chunk-hash>` lines. That's fine for
+//! small files, but verifying it means reading every chunk, and there's no
+//! way to check a subtree without materializing the whole thing. Instead,
+//! chunk hashes are grouped into fixed-size interior node objects, which are
+//! themselves grouped into higher interior nodes, up to a single root. A
+//! node is a newline-delimited list of entries, each either:
+//!
+//!   blob <chunk-hash>   -- a leaf pointing at raw chunk data
+//!   node <node-hash>    -- an interior node, itself a list of entries
+//!
+//! The file's top-level hash (printed by `add`, read by `cat-file`/`verify`)
+//! is the root of this tree.
+
+use crate::hash;
+use crate::hash::HashType;
+use crate::store::{ObjectStore, ObjectType};
+
+/// Children per interior node.
+pub const FANOUT: usize = 16;
+
+#[derive(Debug, Clone)]
+pub enum Entry {
+    Blob(String),
+    Node(String),
+}
+
+impl Entry {
+    fn tag(&self) -> &'static str {
+        match self {
+            Entry::Blob(_) => "blob",
+            Entry::Node(_) => "node",
+        }
+    }
+
+    pub fn hash(&self) -> &str {
+        match self {
+            Entry::Blob(hash) => hash,
+            Entry::Node(hash) => hash,
+        }
+    }
+}
+
+fn format_entries(entries: &[Entry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for entry in entries {
+        out.extend_from_slice(entry.tag().as_bytes());
+        out.push(b' ');
+        out.extend_from_slice(entry.hash().as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Parse a node object's bytes into its entries. Only ever called on bytes
+/// the index already tags as [`ObjectType::Node`], so a failure here means a
+/// genuinely malformed node rather than an ordinary chunk that merely looks
+/// like one.
+pub fn parse_entries(bytes: &[u8]) -> Result<Vec<Entry>, String> {
+    let text = std::str::from_utf8(bytes).map_err(|_| "not valid utf-8".to_string())?;
+
+    let mut entries = Vec::new();
+    for line in text.trim().lines() {
+        let line = line.trim();
+        if let Some(hash) = line.strip_prefix("blob ") {
+            entries.push(Entry::Blob(hash.to_string()));
+        } else if let Some(hash) = line.strip_prefix("node ") {
+            entries.push(Entry::Node(hash.to_string()));
+        } else {
+            return Err(format!("invalid tree entry: {}", line));
+        }
+    }
+    Ok(entries)
+}
+
+/// Build a Merkle tree over a file's chunk hashes (in order), storing every
+/// interior node object, and return the root hash.
+pub fn build_root(object_store: &ObjectStore, hash_type: HashType, leaf_hashes: Vec<String>) -> String {
+    let mut level: Vec<Entry> = leaf_hashes.into_iter().map(Entry::Blob).collect();
+
+    // A single leaf still gets wrapped in a one-entry node, so the returned
+    // hash is always a tree root rather than sometimes a bare chunk hash.
+    if level.len() == 1 {
+        let bytes = format_entries(&level);
+        let hash = hash::hash_bytes(hash_type, &bytes);
+        object_store.store(&hash, &bytes, crate::store::ObjectType::Node);
+        return hash;
+    }
+
+    loop {
+        let mut next_level = Vec::new();
+        for group in level.chunks(FANOUT) {
+            let bytes = format_entries(group);
+            let hash = hash::hash_bytes(hash_type, &bytes);
+            object_store.store(&hash, &bytes, crate::store::ObjectType::Node);
+            next_level.push(Entry::Node(hash));
+        }
+
+        if next_level.len() == 1 {
+            return next_level.into_iter().next().unwrap().hash().to_string();
+        }
+
+        level = next_level;
+    }
+}
+
+/// Walk the tree rooted at `hash`, writing every referenced chunk's bytes to
+/// `out` in order. This is how a file's contents get reconstructed from its
+/// root hash. Dispatches on the stored [`ObjectType`] rather than guessing
+/// from content, so a chunk whose bytes happen to look like a node is never
+/// misread as one.
+pub fn write_tree_bytes(
+    object_store: &ObjectStore,
+    hash: &str,
+    out: &mut impl std::io::Write,
+) -> Result<(), String> {
+    let entry = object_store
+        .entry(hash)
+        .ok_or_else(|| format!("object not found: {}", hash))?;
+    let bytes = object_store.get(hash).unwrap();
+
+    match entry.object_type {
+        ObjectType::Chunk => {
+            out.write_all(&bytes).unwrap();
+            Ok(())
+        }
+        ObjectType::Node => {
+            for entry in parse_entries(&bytes)? {
+                write_tree_bytes(object_store, entry.hash(), out)?;
+            }
+            Ok(())
+        }
+        ObjectType::Tree => Err(format!("{}: not a file (directory tree object)", hash)),
+    }
+}
+
+/// Recursively collect every interior node hash reachable from `root`
+/// (including `root` itself) into `node_hashes`, and add up the logical
+/// bytes referenced by its leaf chunks into `logical_bytes` — counting a
+/// chunk once per place it's referenced, not once overall.
+///
+/// `root` may be a Merkle node (a file's tree, or an interior node of one) or
+/// a [`crate::tree`] directory object; either way everything that isn't a
+/// leaf chunk is overhead and goes into `node_hashes` rather than being
+/// counted as a unique chunk. Dispatches on the stored [`ObjectType`] so a
+/// chunk is never mistaken for a node or tree just because its bytes happen
+/// to parse as one.
+pub fn walk_tree(
+    object_store: &ObjectStore,
+    root: &str,
+    node_hashes: &mut std::collections::HashSet<String>,
+    logical_bytes: &mut u64,
+) {
+    let Some(entry) = object_store.entry(root) else {
+        return;
+    };
+
+    match entry.object_type {
+        ObjectType::Chunk => {
+            *logical_bytes += entry.uncompressed_len;
+        }
+        ObjectType::Node => {
+            node_hashes.insert(root.to_string());
+            let Some(bytes) = object_store.get(root) else {
+                return;
+            };
+            let Ok(entries) = parse_entries(&bytes) else {
+                return;
+            };
+            for entry in entries {
+                walk_tree(object_store, entry.hash(), node_hashes, logical_bytes);
+            }
+        }
+        ObjectType::Tree => {
+            node_hashes.insert(root.to_string());
+            let Some(bytes) = object_store.get(root) else {
+                return;
+            };
+            let Ok(entries) = crate::tree::parse_entries(&bytes) else {
+                return;
+            };
+            for entry in entries {
+                walk_tree(object_store, &entry.hash, node_hashes, logical_bytes);
+            }
+        }
+    }
+}
+
+/// Recompute and check the digest of `hash` and, if it's a Merkle node or
+/// directory tree object, every digest reachable from it. Dispatches on the
+/// stored [`ObjectType`] rather than guessing from content, so a chunk whose
+/// bytes happen to look like a node is never misread as one.
+pub fn verify(object_store: &ObjectStore, hash_type: HashType, hash: &str) -> Result<(), String> {
+    let bytes = object_store
+        .get(hash)
+        .ok_or_else(|| format!("object not found: {}", hash))?;
+
+    let recomputed = hash::hash_bytes(hash_type, &bytes);
+    if recomputed != hash {
+        return Err(format!(
+            "hash mismatch for {}: recomputed {}",
+            hash, recomputed
+        ));
+    }
+
+    let entry = object_store.entry(hash).unwrap();
+    match entry.object_type {
+        ObjectType::Chunk => Ok(()),
+        ObjectType::Node => {
+            for entry in parse_entries(&bytes)? {
+                verify(object_store, hash_type, entry.hash())?;
+            }
+            Ok(())
+        }
+        ObjectType::Tree => {
+            for entry in crate::tree::parse_entries(&bytes)? {
+                verify(object_store, hash_type, &entry.hash)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn temp_store() -> (std::path::PathBuf, ObjectStore) {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!(
+            "blobular-merkle-test-{}-{}",
+            std::process::id(),
+            n
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = ObjectStore::open(&dir);
+        (dir, store)
+    }
+
+    /// A chunk whose content happens to read as a valid one-entry node
+    /// (`blob <hash>`) must still be treated as an opaque leaf by every
+    /// consumer, since the index — not the bytes — says what it is.
+    #[test]
+    fn a_chunk_that_looks_like_a_node_is_never_misread_as_one() {
+        let (dir, store) = temp_store();
+        let hash_type = HashType::Sha1;
+
+        let chunk_bytes = b"blob aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\n";
+        let hash = hash::hash_bytes(hash_type, chunk_bytes);
+        store.store(&hash, chunk_bytes, ObjectType::Chunk);
+
+        assert!(verify(&store, hash_type, &hash).is_ok());
+
+        let mut out = Vec::new();
+        write_tree_bytes(&store, &hash, &mut out).unwrap();
+        assert_eq!(out, chunk_bytes);
+
+        let mut node_hashes = std::collections::HashSet::new();
+        let mut logical_bytes = 0u64;
+        walk_tree(&store, &hash, &mut node_hashes, &mut logical_bytes);
+        assert!(node_hashes.is_empty());
+        assert_eq!(logical_bytes, chunk_bytes.len() as u64);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn build_root_over_multiple_chunks_verifies_and_reassembles() {
+        let (dir, store) = temp_store();
+        let hash_type = HashType::Sha1;
+
+        let chunks: Vec<&[u8]> = vec![b"first-chunk", b"second-chunk", b"third-chunk"];
+        let mut leaf_hashes = Vec::new();
+        for chunk in &chunks {
+            let hash = hash::hash_bytes(hash_type, chunk);
+            store.store(&hash, chunk, ObjectType::Chunk);
+            leaf_hashes.push(hash);
+        }
+
+        let root = build_root(&store, hash_type, leaf_hashes);
+        assert!(verify(&store, hash_type, &root).is_ok());
+
+        let mut out = Vec::new();
+        write_tree_bytes(&store, &root, &mut out).unwrap();
+        assert_eq!(out, chunks.concat());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
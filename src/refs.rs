@@ -0,0 +1,28 @@
+//! Tracks which stored objects are top-level roots produced by `add`: a
+//! file's Merkle root or a directory's tree hash. The object store tags each
+//! object as a chunk, node or tree, but not whether it is a top-level root,
+//! so `stats` (and anything else that needs to walk from an added path down
+//! to its chunks) reads this list to know where to start.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+/// Record `hash` as a top-level root produced by `add`.
+pub fn record(dot_blobular: &Path, hash: &str) {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dot_blobular.join("refs"))
+        .unwrap();
+    writeln!(file, "{}", hash).unwrap();
+}
+
+/// All recorded top-level root hashes, each listed once.
+pub fn read_all(dot_blobular: &Path) -> HashSet<String> {
+    std::fs::read_to_string(dot_blobular.join("refs"))
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.to_string())
+        .collect()
+}
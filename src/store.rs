@@ -0,0 +1,311 @@
+//! The object store: an append-only pack file plus a `sled` index.
+//!
+//! Earlier versions of blobular wrote one zlib-compressed file per object
+//! under `objects/xx/rest...`. For CDC output that's catastrophic — a single
+//! large file explodes into thousands of tiny objects, which is brutal on
+//! inode pressure and makes `cat-file` slow. Instead, chunk bodies are
+//! appended to a single growing pack file (`.blobular/chunks`), and a
+//! `hash -> (offset, length)` index (`.blobular/index`, a `sled` database)
+//! records where each one lives.
+//!
+//! Durability: the pack file is always `sync_data()`-ed before the
+//! corresponding index entry is committed, so the index can never point at
+//! bytes that aren't on disk yet. A `chunks_tail` entry in the index records
+//! the pack length as of the last committed entry; on open, anything beyond
+//! that offset is a partial write from a crash and gets truncated away.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+/// Index key used to track the pack file length as of the last committed
+/// write. Prefixed with underscores so it can never collide with a hex
+/// object hash.
+const TAIL_KEY: &[u8] = b"__chunks_tail__";
+
+/// What an object's bytes mean, recorded explicitly in the index so that
+/// `verify`/`fsck`/stats never have to guess a stored object's shape by
+/// trial-parsing its bytes. Without this, a leaf chunk whose content happens
+/// to decode as UTF-8 lines matching the `merkle`/`tree` format gets
+/// mistaken for a structural node and recursed into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectType {
+    /// A raw, content-defined chunk of file data.
+    Chunk,
+    /// A Merkle interior node (see [`crate::merkle`]).
+    Node,
+    /// A directory tree object (see [`crate::tree`]).
+    Tree,
+}
+
+impl ObjectType {
+    fn to_byte(self) -> u8 {
+        match self {
+            ObjectType::Chunk => 0,
+            ObjectType::Node => 1,
+            ObjectType::Tree => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> ObjectType {
+        match byte {
+            0 => ObjectType::Chunk,
+            1 => ObjectType::Node,
+            2 => ObjectType::Tree,
+            other => panic!("invalid object type byte in index: {}", other),
+        }
+    }
+}
+
+/// Where an object's compressed bytes live in the pack file, plus enough
+/// bookkeeping to report storage savings without decompressing anything.
+#[derive(Debug, Clone, Copy)]
+pub struct IndexEntry {
+    pub offset: u64,
+    pub compressed_len: u64,
+    pub uncompressed_len: u64,
+    pub object_type: ObjectType,
+}
+
+impl IndexEntry {
+    fn to_bytes(self) -> [u8; 25] {
+        let mut bytes = [0u8; 25];
+        bytes[0..8].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.compressed_len.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.uncompressed_len.to_le_bytes());
+        bytes[24] = self.object_type.to_byte();
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> IndexEntry {
+        IndexEntry {
+            offset: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            compressed_len: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            uncompressed_len: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            object_type: ObjectType::from_byte(bytes[24]),
+        }
+    }
+}
+
+pub struct ObjectStore {
+    pack_path: PathBuf,
+    index: sled::Db,
+}
+
+impl ObjectStore {
+    /// Open the pack file and index, truncating the pack back to the last
+    /// committed length if a previous write crashed mid-append.
+    pub fn open(dot_blobular: &Path) -> ObjectStore {
+        let pack_path = dot_blobular.join("chunks");
+        if !pack_path.is_file() {
+            std::fs::File::create(&pack_path).unwrap();
+        }
+
+        let index = sled::open(dot_blobular.join("index")).unwrap();
+        let store = ObjectStore { pack_path, index };
+        store.recover();
+        store
+    }
+
+    fn recover(&self) {
+        let tail = self.chunks_tail();
+        let actual_len = std::fs::metadata(&self.pack_path).unwrap().len();
+        if actual_len > tail {
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&self.pack_path)
+                .unwrap();
+            file.set_len(tail).unwrap();
+        }
+    }
+
+    fn chunks_tail(&self) -> u64 {
+        match self.index.get(TAIL_KEY).unwrap() {
+            Some(bytes) => u64::from_le_bytes(bytes.as_ref().try_into().unwrap()),
+            None => 0,
+        }
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.index.contains_key(hash.as_bytes()).unwrap()
+    }
+
+    /// Append `bytes` (compressed) to the pack file and record its location
+    /// and `object_type` in the index. A no-op if the object already exists.
+    /// Returns whether the object was newly inserted.
+    pub fn store(&self, hash: &str, bytes: &[u8], object_type: ObjectType) -> bool {
+        if self.contains(hash) {
+            return false;
+        }
+
+        let mut compressed = Vec::new();
+        {
+            let mut encoder = ZlibEncoder::new(&mut compressed, Compression::default());
+            encoder.write_all(bytes).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&self.pack_path)
+            .unwrap();
+        let offset = file.metadata().unwrap().len();
+        file.write_all(&compressed).unwrap();
+        // The index must never reference bytes that aren't durably on disk yet.
+        file.sync_data().unwrap();
+
+        let entry = IndexEntry {
+            offset,
+            compressed_len: compressed.len() as u64,
+            uncompressed_len: bytes.len() as u64,
+            object_type,
+        };
+        // The hash entry and the tail update must land together: if a crash
+        // separated them, the hash entry could be committed while the tail
+        // stays stale, and `recover()` would truncate the pack file back past
+        // bytes that entry still points to.
+        let mut batch = sled::Batch::default();
+        batch.insert(hash.as_bytes(), &entry.to_bytes());
+        batch.insert(TAIL_KEY, &(offset + compressed.len() as u64).to_le_bytes());
+        self.index.apply_batch(batch).unwrap();
+        self.index.flush().unwrap();
+        true
+    }
+
+    pub fn entry(&self, hash: &str) -> Option<IndexEntry> {
+        self.index
+            .get(hash.as_bytes())
+            .unwrap()
+            .map(|bytes| IndexEntry::from_bytes(&bytes))
+    }
+
+    /// Read and decompress an object's bytes.
+    pub fn get(&self, hash: &str) -> Option<Vec<u8>> {
+        let entry = self.entry(hash)?;
+
+        let mut file = std::fs::File::open(&self.pack_path).unwrap();
+        file.seek(SeekFrom::Start(entry.offset)).unwrap();
+        let mut compressed = vec![0u8; entry.compressed_len as usize];
+        file.read_exact(&mut compressed).unwrap();
+
+        let mut decoder = ZlibDecoder::new(&compressed[..]);
+        let mut out = Vec::new();
+        decoder.read_to_end(&mut out).unwrap();
+        Some(out)
+    }
+
+    /// All object hashes (excluding internal bookkeeping keys) that start
+    /// with `prefix`.
+    pub fn hashes_with_prefix(&self, prefix: &str) -> Vec<String> {
+        self.index
+            .scan_prefix(prefix.as_bytes())
+            .filter_map(|entry| entry.ok())
+            .filter(|(key, _)| key.as_ref() != TAIL_KEY)
+            .map(|(key, _)| String::from_utf8(key.to_vec()).unwrap())
+            .collect()
+    }
+
+    /// Iterate over every stored object hash and its index entry.
+    pub fn iter(&self) -> impl Iterator<Item = (String, IndexEntry)> + '_ {
+        self.index
+            .iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|(key, _)| key.as_ref() != TAIL_KEY)
+            .map(|(key, value)| {
+                (
+                    String::from_utf8(key.to_vec()).unwrap(),
+                    IndexEntry::from_bytes(&value),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// A fresh `.blobular`-shaped scratch directory, unique per test.
+    fn temp_dot_blobular() -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!(
+            "blobular-store-test-{}-{}",
+            std::process::id(),
+            n
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn store_and_get_round_trip() {
+        let dir = temp_dot_blobular();
+        let store = ObjectStore::open(&dir);
+
+        assert!(store.store("deadbeef", b"hello world", ObjectType::Chunk));
+        assert_eq!(store.get("deadbeef").unwrap(), b"hello world");
+        assert_eq!(
+            store.entry("deadbeef").unwrap().object_type,
+            ObjectType::Chunk
+        );
+
+        // Storing the same hash again is a no-op.
+        assert!(!store.store("deadbeef", b"hello world", ObjectType::Chunk));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn recovers_from_a_partial_trailing_write() {
+        let dir = temp_dot_blobular();
+        {
+            let store = ObjectStore::open(&dir);
+            store.store("aaaa", b"first", ObjectType::Chunk);
+            store.store("bbbb", b"second", ObjectType::Chunk);
+        }
+
+        // Simulate a crash mid-append: bytes landed in the pack file, but no
+        // index entry (and so no tail update) was ever committed for them.
+        let pack_path = dir.join("chunks");
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&pack_path)
+            .unwrap();
+        file.write_all(b"garbage-from-a-crashed-write").unwrap();
+        drop(file);
+
+        let len_with_garbage = std::fs::metadata(&pack_path).unwrap().len();
+
+        let store = ObjectStore::open(&dir);
+        let len_after_recovery = std::fs::metadata(&pack_path).unwrap().len();
+
+        assert!(len_after_recovery < len_with_garbage);
+        assert_eq!(len_after_recovery, store.chunks_tail());
+        assert_eq!(store.get("aaaa").unwrap(), b"first");
+        assert_eq!(store.get("bbbb").unwrap(), b"second");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn hash_entry_and_tail_are_committed_together() {
+        let dir = temp_dot_blobular();
+        let store = ObjectStore::open(&dir);
+
+        store.store("cccc", b"payload", ObjectType::Chunk);
+
+        // The tail must always agree with the pack length right after a
+        // `store()` call — if the hash entry and tail update could land as
+        // two separate writes, a crash between them would leave the tail
+        // stale and `recover()` would truncate away an already-indexed
+        // object on the next open.
+        let pack_len = std::fs::metadata(dir.join("chunks")).unwrap().len();
+        assert_eq!(store.chunks_tail(), pack_len);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,117 @@
+//! Directory tree objects.
+//!
+//! A tree object is a directory snapshot: one line per entry, each
+//! `<mode> <name> <hash>`. `mode` is a Unix-style file mode (`100644` for a
+//! regular file, `100755` for an executable one, `40000` for a
+//! subdirectory, written in octal without a leading zero); `name` is the entry's bare file name; `hash` is a Merkle
+//! root (see [`crate::merkle`]) for a file entry, or another tree's hash for
+//! a subdirectory entry.
+
+use crate::hash;
+use crate::hash::HashType;
+use crate::store::ObjectStore;
+
+/// Mask isolating the file-type bits of a mode, mirroring `S_IFMT`.
+pub const TYPE_MASK: u32 = 0o170000;
+pub const TYPE_DIR: u32 = 0o040000;
+pub const TYPE_FILE: u32 = 0o100000;
+
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    pub mode: u32,
+    pub name: String,
+    pub hash: String,
+}
+
+fn format_entries(entries: &[TreeEntry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for entry in entries {
+        out.extend_from_slice(format!("{:o} {} {}\n", entry.mode, entry.name, entry.hash).as_bytes());
+    }
+    out
+}
+
+/// Parse a tree object's bytes into its entries.
+///
+/// A name may itself contain spaces, so it can't be split out with a fixed
+/// word count: `mode` is taken as the first space-delimited token, `hash` as
+/// the last (hashes are fixed-length hex with no spaces), and whatever's left
+/// in between — however many spaces it contains — is the name.
+pub fn parse_entries(bytes: &[u8]) -> Result<Vec<TreeEntry>, String> {
+    let text = std::str::from_utf8(bytes).map_err(|_| "not valid utf-8".to_string())?;
+
+    let mut entries = Vec::new();
+    for line in text.trim().lines() {
+        let line = line.trim();
+        let mut head = line.splitn(2, ' ');
+        let (mode, rest) = match (head.next(), head.next()) {
+            (Some(mode), Some(rest)) => (mode, rest),
+            _ => return Err(format!("invalid tree entry: {}", line)),
+        };
+
+        let mut tail = rest.rsplitn(2, ' ');
+        let (hash, name) = match (tail.next(), tail.next()) {
+            (Some(hash), Some(name)) => (hash, name),
+            _ => return Err(format!("invalid tree entry: {}", line)),
+        };
+
+        let mode = u32::from_str_radix(mode, 8)
+            .map_err(|_| format!("invalid mode in tree entry: {}", line))?;
+
+        entries.push(TreeEntry {
+            mode,
+            name: name.to_string(),
+            hash: hash.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Store a tree object built from `entries` and return its hash.
+pub fn store_tree(object_store: &ObjectStore, hash_type: HashType, entries: &[TreeEntry]) -> String {
+    let bytes = format_entries(entries);
+    let hash = hash::hash_bytes(hash_type, &bytes);
+    object_store.store(&hash, &bytes, crate::store::ObjectType::Tree);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_entries_with_spaces_in_names() {
+        let entries = vec![
+            TreeEntry {
+                mode: 0o100644,
+                name: "my notes.txt".to_string(),
+                hash: "deadbeef1234".to_string(),
+            },
+            TreeEntry {
+                mode: 0o040000,
+                name: "a directory with spaces".to_string(),
+                hash: "cafebabe5678".to_string(),
+            },
+        ];
+
+        let bytes = format_entries(&entries);
+        let parsed = parse_entries(&bytes).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].mode, 0o100644);
+        assert_eq!(parsed[0].name, "my notes.txt");
+        assert_eq!(parsed[0].hash, "deadbeef1234");
+        assert_eq!(parsed[1].name, "a directory with spaces");
+        assert_eq!(parsed[1].hash, "cafebabe5678");
+    }
+
+    #[test]
+    fn rejects_a_line_with_no_hash() {
+        assert!(parse_entries(b"100644 onlyname\n").is_err());
+    }
+
+    #[test]
+    fn rejects_a_non_octal_mode() {
+        assert!(parse_entries(b"zzzzzz name hash\n").is_err());
+    }
+}